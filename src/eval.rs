@@ -0,0 +1,598 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use super::{FileLocation, SExp, Spanned};
+
+#[derive(Debug)]
+pub enum EvalError{
+	UnboundSymbol{ loc : FileLocation, name : String },
+	NotCallable{ loc : FileLocation, msg : String },
+	ArityMismatch{ loc : FileLocation, msg : String },
+	WrongType{ loc : FileLocation, msg : String },
+	Overflow{ loc : FileLocation, msg : String },
+}
+
+impl fmt::Display for EvalError {
+	fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			EvalError::UnboundSymbol{ ref loc, ref name } =>
+				write!(f, "{}:{}:{}: unbound symbol '{}'", loc.file, loc.line, loc.col, name),
+			EvalError::NotCallable{ ref loc, ref msg } =>
+				write!(f, "{}:{}:{}: {}", loc.file, loc.line, loc.col, msg),
+			EvalError::ArityMismatch{ ref loc, ref msg } =>
+				write!(f, "{}:{}:{}: {}", loc.file, loc.line, loc.col, msg),
+			EvalError::WrongType{ ref loc, ref msg } =>
+				write!(f, "{}:{}:{}: {}", loc.file, loc.line, loc.col, msg),
+			EvalError::Overflow{ ref loc, ref msg } =>
+				write!(f, "{}:{}:{}: {}", loc.file, loc.line, loc.col, msg),
+		}
+	}
+}
+
+#[derive(Clone)]
+pub enum Value{
+	Integer(isize),
+	Float(f64),
+	Str(String),
+	Symbol(String),
+	List(Vec<Value>),
+	Pair(Box<Value>, Box<Value>),
+	Nil,
+	Builtin(&'static str, fn(&[Value], &FileLocation) -> Result<Value, EvalError>),
+	Lambda(Rc<Lambda>),
+}
+
+pub struct Lambda{
+	params : Vec<String>,
+	body : SExp,
+	body_loc : FileLocation,
+	env : Rc<RefCell<Environment>>,
+}
+
+impl fmt::Display for Value {
+	fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Value::Integer(n) => write!(f, "{}", n),
+			Value::Float(n) => write!(f, "{}", n),
+			Value::Str(ref s) => write!(f, "\"{}\"", s),
+			Value::Symbol(ref s) => write!(f, "{}", s),
+			Value::Nil => write!(f, "()"),
+			Value::List(ref items) => {
+				try!(write!(f, "("));
+				for (i, item) in items.iter().enumerate() {
+					if i > 0 {
+						try!(write!(f, " "));
+					}
+					try!(write!(f, "{}", item));
+				}
+				write!(f, ")")
+			}
+			Value::Pair(ref car, ref cdr) => write!(f, "({} . {})", car, cdr),
+			Value::Builtin(name, _) => write!(f, "#<builtin:{}>", name),
+			Value::Lambda(_) => write!(f, "#<lambda>"),
+		}
+	}
+}
+
+pub struct Environment{
+	vars : HashMap<String, Value>,
+	parent : Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+	pub fn new() -> Rc<RefCell<Environment>> {
+		Rc::new(RefCell::new(Environment{ vars: HashMap::new(), parent: None }))
+	}
+
+	pub fn child(parent : &Rc<RefCell<Environment>>) -> Rc<RefCell<Environment>> {
+		Rc::new(RefCell::new(Environment{ vars: HashMap::new(), parent: Some(parent.clone()) }))
+	}
+
+	pub fn global() -> Rc<RefCell<Environment>> {
+		let env = Environment::new();
+		{
+			let mut scope = env.borrow_mut();
+			scope.define("+".to_string(), Value::Builtin("+", builtin_add));
+			scope.define("-".to_string(), Value::Builtin("-", builtin_sub));
+			scope.define("*".to_string(), Value::Builtin("*", builtin_mul));
+			scope.define("=".to_string(), Value::Builtin("=", builtin_eq));
+			scope.define("car".to_string(), Value::Builtin("car", builtin_car));
+			scope.define("cdr".to_string(), Value::Builtin("cdr", builtin_cdr));
+			scope.define("cons".to_string(), Value::Builtin("cons", builtin_cons));
+		}
+		env
+	}
+
+	pub fn define(&mut self, name : String, value : Value) {
+		self.vars.insert(name, value);
+	}
+
+	pub fn get(&self, name : &str) -> Option<Value> {
+		if let Some(value) = self.vars.get(name) {
+			return Some(value.clone());
+		}
+
+		match self.parent {
+			Some(ref parent) => parent.borrow().get(name),
+			None => None,
+		}
+	}
+}
+
+fn list_items(list : &SExp) -> Vec<&Spanned<SExp>> {
+	let mut items = Vec::new();
+	let mut cur = list;
+
+	loop {
+		match *cur {
+			SExp::Cons(ref head, ref tail) => {
+				items.push(head.as_ref());
+				cur = tail;
+			}
+			_ => break,
+		}
+	}
+
+	items
+}
+
+fn sexp_to_value(expr : &SExp) -> Value {
+	match *expr {
+		SExp::Integer(n) => Value::Integer(n),
+		SExp::Float(n) => Value::Float(n),
+		SExp::String(ref s) => Value::Str(s.clone()),
+		SExp::Symbol(ref s) => Value::Symbol(s.clone()),
+		SExp::Nil => Value::Nil,
+		SExp::Cons(..) => Value::List(list_items(expr).iter().map(|item| sexp_to_value(&item.value)).collect()),
+	}
+}
+
+fn is_truthy(value : &Value) -> bool {
+	match *value {
+		Value::Nil => false,
+		Value::Integer(0) => false,
+		_ => true,
+	}
+}
+
+pub fn eval(expr : &SExp, loc : &FileLocation, env : &Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+	match *expr {
+		SExp::Integer(n) => Ok(Value::Integer(n)),
+		SExp::Float(n) => Ok(Value::Float(n)),
+		SExp::String(ref s) => Ok(Value::Str(s.clone())),
+		SExp::Nil => Ok(Value::Nil),
+		SExp::Symbol(ref name) => {
+			env.borrow().get(name).ok_or_else(|| EvalError::UnboundSymbol{
+				loc: loc.clone(),
+				name: name.clone(),
+			})
+		}
+		SExp::Cons(ref head, ref tail) => eval_application(head, tail, loc, env),
+	}
+}
+
+fn eval_application(head : &Spanned<SExp>, tail : &SExp, loc : &FileLocation, env : &Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+	if let SExp::Symbol(ref name) = head.value {
+		match name.as_str() {
+			"quote"  => return eval_quote(tail, loc),
+			"if"     => return eval_if(tail, loc, env),
+			"define" => return eval_define(tail, loc, env),
+			"lambda" => return eval_lambda(tail, loc, env),
+			"let"    => return eval_let(tail, loc, env),
+			_ => {}
+		}
+	}
+
+	let callee = try!(eval(&head.value, &head.start, env));
+
+	let mut args = Vec::new();
+	for item in list_items(tail) {
+		args.push(try!(eval(&item.value, &item.start, env)));
+	}
+
+	apply(callee, &args, loc)
+}
+
+fn eval_quote(tail : &SExp, loc : &FileLocation) -> Result<Value, EvalError> {
+	let items = list_items(tail);
+	if items.len() != 1 {
+		return Err(EvalError::ArityMismatch{
+			loc: loc.clone(),
+			msg: format!("quote expects exactly 1 argument, got {}", items.len()),
+		});
+	}
+
+	Ok(sexp_to_value(&items[0].value))
+}
+
+fn eval_if(tail : &SExp, loc : &FileLocation, env : &Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+	let items = list_items(tail);
+	if items.len() != 3 {
+		return Err(EvalError::ArityMismatch{
+			loc: loc.clone(),
+			msg: format!("if expects exactly 3 arguments, got {}", items.len()),
+		});
+	}
+
+	let cond = try!(eval(&items[0].value, &items[0].start, env));
+	if is_truthy(&cond) {
+		eval(&items[1].value, &items[1].start, env)
+	} else {
+		eval(&items[2].value, &items[2].start, env)
+	}
+}
+
+fn eval_define(tail : &SExp, loc : &FileLocation, env : &Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+	let items = list_items(tail);
+	if items.len() != 2 {
+		return Err(EvalError::ArityMismatch{
+			loc: loc.clone(),
+			msg: format!("define expects exactly 2 arguments, got {}", items.len()),
+		});
+	}
+
+	let name = match items[0].value {
+		SExp::Symbol(ref s) => s.clone(),
+		_ => return Err(EvalError::WrongType{
+			loc: items[0].start.clone(),
+			msg: "define expects a symbol as its first argument".to_string(),
+		}),
+	};
+
+	let value = try!(eval(&items[1].value, &items[1].start, env));
+	env.borrow_mut().define(name, value.clone());
+	Ok(value)
+}
+
+fn eval_lambda(tail : &SExp, loc : &FileLocation, env : &Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+	let items = list_items(tail);
+	if items.len() != 2 {
+		return Err(EvalError::ArityMismatch{
+			loc: loc.clone(),
+			msg: format!("lambda expects exactly 2 arguments, got {}", items.len()),
+		});
+	}
+
+	let params = try!(parse_params(&items[0].value));
+
+	Ok(Value::Lambda(Rc::new(Lambda{
+		params,
+		body: items[1].value.clone(),
+		body_loc: items[1].start.clone(),
+		env: env.clone(),
+	})))
+}
+
+fn parse_params(expr : &SExp) -> Result<Vec<String>, EvalError> {
+	let mut params = Vec::new();
+
+	for item in list_items(expr) {
+		match item.value {
+			SExp::Symbol(ref s) => params.push(s.clone()),
+			_ => return Err(EvalError::WrongType{
+				loc: item.start.clone(),
+				msg: "lambda parameter list must contain only symbols".to_string(),
+			}),
+		}
+	}
+
+	Ok(params)
+}
+
+fn eval_let(tail : &SExp, loc : &FileLocation, env : &Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+	let items = list_items(tail);
+	if items.len() != 2 {
+		return Err(EvalError::ArityMismatch{
+			loc: loc.clone(),
+			msg: format!("let expects exactly 2 arguments, got {}", items.len()),
+		});
+	}
+
+	let child = Environment::child(env);
+
+	for binding in list_items(&items[0].value) {
+		let pair = list_items(&binding.value);
+		if pair.len() != 2 {
+			return Err(EvalError::ArityMismatch{
+				loc: binding.start.clone(),
+				msg: format!("let binding expects exactly 2 elements, got {}", pair.len()),
+			});
+		}
+
+		let name = match pair[0].value {
+			SExp::Symbol(ref s) => s.clone(),
+			_ => return Err(EvalError::WrongType{
+				loc: pair[0].start.clone(),
+				msg: "let binding name must be a symbol".to_string(),
+			}),
+		};
+
+		let value = try!(eval(&pair[1].value, &pair[1].start, env));
+		child.borrow_mut().define(name, value);
+	}
+
+	eval(&items[1].value, &items[1].start, &child)
+}
+
+fn apply(callee : Value, args : &[Value], loc : &FileLocation) -> Result<Value, EvalError> {
+	match callee {
+		Value::Builtin(_, f) => f(args, loc),
+		Value::Lambda(ref lambda) => {
+			if args.len() != lambda.params.len() {
+				return Err(EvalError::ArityMismatch{
+					loc: loc.clone(),
+					msg: format!("lambda expects {} argument(s), got {}", lambda.params.len(), args.len()),
+				});
+			}
+
+			let call_env = Environment::child(&lambda.env);
+			for (param, arg) in lambda.params.iter().zip(args.iter()) {
+				call_env.borrow_mut().define(param.clone(), arg.clone());
+			}
+
+			eval(&lambda.body, &lambda.body_loc, &call_env)
+		}
+		_ => Err(EvalError::NotCallable{
+			loc: loc.clone(),
+			msg: "value is not callable".to_string(),
+		}),
+	}
+}
+
+fn all_integers(args : &[Value]) -> bool {
+	args.iter().all(|v| match *v { Value::Integer(_) => true, _ => false })
+}
+
+fn numeric_values(args : &[Value], loc : &FileLocation) -> Result<Vec<f64>, EvalError> {
+	let mut out = Vec::new();
+
+	for arg in args {
+		match *arg {
+			Value::Integer(n) => out.push(n as f64),
+			Value::Float(n) => out.push(n),
+			_ => return Err(EvalError::WrongType{
+				loc: loc.clone(),
+				msg: "expected a number".to_string(),
+			}),
+		}
+	}
+
+	Ok(out)
+}
+
+fn error_overflow(loc : &FileLocation, op : &str) -> EvalError {
+	EvalError::Overflow{ loc: loc.clone(), msg: format!("integer overflow in '{}'", op) }
+}
+
+fn checked_sum(ints : &[isize], loc : &FileLocation) -> Result<isize, EvalError> {
+	let mut acc : isize = 0;
+	for n in ints {
+		acc = try!(acc.checked_add(*n).ok_or_else(|| error_overflow(loc, "+")));
+	}
+	Ok(acc)
+}
+
+fn checked_product(ints : &[isize], loc : &FileLocation) -> Result<isize, EvalError> {
+	let mut acc : isize = 1;
+	for n in ints {
+		acc = try!(acc.checked_mul(*n).ok_or_else(|| error_overflow(loc, "*")));
+	}
+	Ok(acc)
+}
+
+fn builtin_add(args : &[Value], loc : &FileLocation) -> Result<Value, EvalError> {
+	if all_integers(args) {
+		let ints : Vec<isize> = args.iter().map(|v| match *v { Value::Integer(n) => n, _ => unreachable!() }).collect();
+		Ok(Value::Integer(try!(checked_sum(&ints, loc))))
+	} else {
+		let nums = try!(numeric_values(args, loc));
+		Ok(Value::Float(nums.iter().sum()))
+	}
+}
+
+fn builtin_sub(args : &[Value], loc : &FileLocation) -> Result<Value, EvalError> {
+	if args.is_empty() {
+		return Err(EvalError::ArityMismatch{ loc: loc.clone(), msg: "- expects at least 1 argument".to_string() });
+	}
+
+	if all_integers(args) {
+		let ints : Vec<isize> = args.iter().map(|v| match *v { Value::Integer(n) => n, _ => unreachable!() }).collect();
+		if ints.len() == 1 {
+			ints[0].checked_neg().map(Value::Integer).ok_or_else(|| error_overflow(loc, "-"))
+		} else {
+			let mut acc = ints[0];
+			for n in &ints[1..] {
+				acc = try!(acc.checked_sub(*n).ok_or_else(|| error_overflow(loc, "-")));
+			}
+			Ok(Value::Integer(acc))
+		}
+	} else {
+		let nums = try!(numeric_values(args, loc));
+		if nums.len() == 1 {
+			Ok(Value::Float(-nums[0]))
+		} else {
+			Ok(Value::Float(nums[1..].iter().fold(nums[0], |acc, n| acc - n)))
+		}
+	}
+}
+
+fn builtin_mul(args : &[Value], loc : &FileLocation) -> Result<Value, EvalError> {
+	if all_integers(args) {
+		let ints : Vec<isize> = args.iter().map(|v| match *v { Value::Integer(n) => n, _ => unreachable!() }).collect();
+		Ok(Value::Integer(try!(checked_product(&ints, loc))))
+	} else {
+		let nums = try!(numeric_values(args, loc));
+		Ok(Value::Float(nums.iter().product()))
+	}
+}
+
+fn values_equal(a : &Value, b : &Value) -> bool {
+	match (a, b) {
+		(Value::Integer(x), Value::Integer(y)) => x == y,
+		(Value::Float(x), Value::Float(y)) => x == y,
+		(Value::Integer(x), Value::Float(y)) | (Value::Float(y), Value::Integer(x)) => *x as f64 == *y,
+		(Value::Str(x), Value::Str(y)) => x == y,
+		(Value::Symbol(x), Value::Symbol(y)) => x == y,
+		(Value::Nil, Value::Nil) => true,
+		(Value::Pair(a1, d1), Value::Pair(a2, d2)) =>
+			values_equal(a1, a2) && values_equal(d1, d2),
+		_ => false,
+	}
+}
+
+fn builtin_eq(args : &[Value], loc : &FileLocation) -> Result<Value, EvalError> {
+	if args.len() < 2 {
+		return Err(EvalError::ArityMismatch{ loc: loc.clone(), msg: "= expects at least 2 arguments".to_string() });
+	}
+
+	let equal = args.windows(2).all(|pair| values_equal(&pair[0], &pair[1]));
+	Ok(if equal { Value::Integer(1) } else { Value::Nil })
+}
+
+fn builtin_car(args : &[Value], loc : &FileLocation) -> Result<Value, EvalError> {
+	if args.len() != 1 {
+		return Err(EvalError::ArityMismatch{ loc: loc.clone(), msg: format!("car expects exactly 1 argument, got {}", args.len()) });
+	}
+
+	match args[0] {
+		Value::List(ref items) => items.first().cloned().ok_or_else(|| EvalError::WrongType{
+			loc: loc.clone(),
+			msg: "car of empty list".to_string(),
+		}),
+		Value::Pair(ref car, _) => Ok((**car).clone()),
+		_ => Err(EvalError::WrongType{ loc: loc.clone(), msg: "car expects a list or pair".to_string() }),
+	}
+}
+
+fn builtin_cdr(args : &[Value], loc : &FileLocation) -> Result<Value, EvalError> {
+	if args.len() != 1 {
+		return Err(EvalError::ArityMismatch{ loc: loc.clone(), msg: format!("cdr expects exactly 1 argument, got {}", args.len()) });
+	}
+
+	match args[0] {
+		Value::List(ref items) if !items.is_empty() => Ok(Value::List(items[1..].to_vec())),
+		Value::List(_) => Err(EvalError::WrongType{ loc: loc.clone(), msg: "cdr of empty list".to_string() }),
+		Value::Pair(_, ref cdr) => Ok((**cdr).clone()),
+		_ => Err(EvalError::WrongType{ loc: loc.clone(), msg: "cdr expects a list or pair".to_string() }),
+	}
+}
+
+fn builtin_cons(args : &[Value], loc : &FileLocation) -> Result<Value, EvalError> {
+	if args.len() != 2 {
+		return Err(EvalError::ArityMismatch{ loc: loc.clone(), msg: format!("cons expects exactly 2 arguments, got {}", args.len()) });
+	}
+
+	match args[1] {
+		Value::List(ref rest) => {
+			let mut items = vec![args[0].clone()];
+			items.extend(rest.iter().cloned());
+			Ok(Value::List(items))
+		}
+		Value::Nil => Ok(Value::List(vec![args[0].clone()])),
+		ref other => Ok(Value::Pair(Box::new(args[0].clone()), Box::new(other.clone()))),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn loc() -> FileLocation {
+		FileLocation{ file: "<test>".to_string(), line: 1, col: 0 }
+	}
+
+	fn eval_str(src : &str) -> Result<Value, EvalError> {
+		let parsed = super::super::parse_buffered(src).unwrap_or_else(|e| panic!("parse error: {:?}", e));
+		eval(&parsed.value, &parsed.start, &Environment::global())
+	}
+
+	#[test]
+	fn add_overflow_is_an_error() {
+		match builtin_add(&[Value::Integer(isize::max_value()), Value::Integer(1)], &loc()) {
+			Err(EvalError::Overflow{ .. }) => {}
+			other => panic!("expected an overflow error, got {:?}", other.map(|v| format!("{}", v))),
+		}
+	}
+
+	#[test]
+	fn sub_overflow_is_an_error() {
+		match builtin_sub(&[Value::Integer(isize::min_value()), Value::Integer(1)], &loc()) {
+			Err(EvalError::Overflow{ .. }) => {}
+			other => panic!("expected an overflow error, got {:?}", other.map(|v| format!("{}", v))),
+		}
+	}
+
+	#[test]
+	fn negating_min_int_is_an_error() {
+		match builtin_sub(&[Value::Integer(isize::min_value())], &loc()) {
+			Err(EvalError::Overflow{ .. }) => {}
+			other => panic!("expected an overflow error, got {:?}", other.map(|v| format!("{}", v))),
+		}
+	}
+
+	#[test]
+	fn mul_overflow_is_an_error() {
+		match builtin_mul(&[Value::Integer(isize::max_value()), Value::Integer(2)], &loc()) {
+			Err(EvalError::Overflow{ .. }) => {}
+			other => panic!("expected an overflow error, got {:?}", other.map(|v| format!("{}", v))),
+		}
+	}
+
+	#[test]
+	fn arithmetic_without_overflow_is_unaffected() {
+		match builtin_add(&[Value::Integer(1), Value::Integer(2), Value::Integer(3)], &loc()) {
+			Ok(Value::Integer(n)) => assert_eq!(n, 6),
+			other => panic!("expected 6, got {:?}", other.map(|v| format!("{}", v))),
+		}
+	}
+
+	#[test]
+	fn sub_requires_at_least_one_argument() {
+		match builtin_sub(&[], &loc()) {
+			Err(EvalError::ArityMismatch{ .. }) => {}
+			other => panic!("expected an arity error, got {:?}", other.map(|v| format!("{}", v))),
+		}
+	}
+
+	#[test]
+	fn cons_with_non_list_cdr_builds_a_pair() {
+		match builtin_cons(&[Value::Integer(1), Value::Integer(2)], &loc()) {
+			Ok(Value::Pair(ref car, ref cdr)) => {
+				match **car { Value::Integer(n) => assert_eq!(n, 1), _ => panic!("expected car to be 1") }
+				match **cdr { Value::Integer(n) => assert_eq!(n, 2), _ => panic!("expected cdr to be 2") }
+			}
+			other => panic!("expected a pair, got {:?}", other.map(|v| format!("{}", v))),
+		}
+	}
+
+	#[test]
+	fn cons_with_list_cdr_extends_the_list() {
+		match builtin_cons(&[Value::Integer(1), Value::List(vec![Value::Integer(2), Value::Integer(3)])], &loc()) {
+			Ok(Value::List(ref items)) => assert_eq!(items.len(), 3),
+			other => panic!("expected a 3-element list, got {:?}", other.map(|v| format!("{}", v))),
+		}
+	}
+
+	#[test]
+	fn car_and_cdr_of_a_pair() {
+		let pair = Value::Pair(Box::new(Value::Integer(1)), Box::new(Value::Integer(2)));
+		match builtin_car(&[pair.clone()], &loc()) {
+			Ok(Value::Integer(n)) => assert_eq!(n, 1),
+			other => panic!("expected 1, got {:?}", other.map(|v| format!("{}", v))),
+		}
+		match builtin_cdr(&[pair], &loc()) {
+			Ok(Value::Integer(n)) => assert_eq!(n, 2),
+			other => panic!("expected 2, got {:?}", other.map(|v| format!("{}", v))),
+		}
+	}
+
+	#[test]
+	fn cons_through_the_evaluator() {
+		match eval_str("(cons 1 2)") {
+			Ok(Value::Pair(..)) => {}
+			other => panic!("expected a pair, got {:?}", other.map(|v| format!("{}", v))),
+		}
+	}
+}
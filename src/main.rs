@@ -1,11 +1,37 @@
 #![allow(dead_code)]
 
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+mod eval;
+
+#[derive(Clone)]
 enum SExp {
-	Cons(Box<SExp>, Box<SExp>),
+	Cons(Box<Spanned<SExp>>, Box<SExp>),
 	Nil,
 	Symbol(String),
 	String(String),
 	Integer(isize),
+	Float(f64),
+}
+
+#[derive(Clone)]
+struct Spanned<T>{
+	value : T,
+	start : FileLocation,
+	end : FileLocation,
+}
+
+impl<T> Spanned<T> {
+	fn new(value : T, start : FileLocation, end : FileLocation) -> Spanned<T> {
+		Spanned{ value, start, end }
+	}
+}
+
+impl<T : fmt::Display> fmt::Display for Spanned<T> {
+	fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.value)
+	}
 }
 
 
@@ -14,7 +40,8 @@ enum ReadError{
 	EarlyEOF{ loc : FileLocation,	msg : String },
 	WrongChar{ loc : FileLocation,	msg : String },
 	ParenMismatch{ loc : FileLocation,	msg : String },
-	NotImplemented,
+	MalformedNumber{ loc : FileLocation,	msg : String },
+	MalformedEscape{ loc : FileLocation,	msg : String },
 }
 
 #[derive(Debug, Clone)]
@@ -24,13 +51,70 @@ struct FileLocation{
 	col : usize,
 }
 
-struct SExpParser<'a>{
+#[derive(Debug, Clone, PartialEq)]
+enum TokenClass{
+	OpenParen,
+	CloseParen,
+	StringStart,
+	Number,
+	Symbol,
+	QuotePrefix,
+}
+
+impl TokenClass{
+	fn describe(&self) -> &'static str {
+		match *self {
+			TokenClass::OpenParen   => "'('",
+			TokenClass::CloseParen  => "')'",
+			TokenClass::StringStart => "'\"'",
+			TokenClass::Number      => "digit",
+			TokenClass::Symbol      => "symbol",
+			TokenClass::QuotePrefix => "one of ', `, ,",
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+enum TokenType{
+	OpenParen(char),
+	CloseParen(char),
+	Str(String),
+	Int(isize),
+	Float(f64),
+	Sym(String),
+	Quote,
+	Quasiquote,
+	Unquote,
+	UnquoteSplicing,
+}
+
+impl TokenType{
+	fn describe(&self) -> String {
+		match *self {
+			TokenType::OpenParen(c) | TokenType::CloseParen(c) => format!("'{}'", c),
+			TokenType::Str(_) => "a string".to_string(),
+			TokenType::Int(_) | TokenType::Float(_) => "a number".to_string(),
+			TokenType::Sym(ref s) => format!("symbol '{}'", s),
+			TokenType::Quote => "'\''".to_string(),
+			TokenType::Quasiquote => "'`'".to_string(),
+			TokenType::Unquote | TokenType::UnquoteSplicing => "','".to_string(),
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+struct Token{
+	ty : TokenType,
+	loc : FileLocation,
+}
+
+struct Lexer<'a>{
 	loc : FileLocation,
 	iter : &'a mut Iterator<Item=char>,
 	next_char : Option<char>,
 }
 
-impl<'a> SExpParser<'a> {
+impl<'a> Lexer<'a> {
 	fn eat_white_space(& mut self) {
 		while let Some(c) = self.peek(){
 			if c.is_whitespace() {
@@ -40,61 +124,63 @@ impl<'a> SExpParser<'a> {
 			}
 		}
 	}
-	
-	fn read_sexp(& mut self) -> Result<SExp, ReadError> {
-		let c = try!(self.peek().ok_or(self.error_eof()));
-		
-		if c.is_open_paren() {
-			self.read_list()
-		} else if c == '-' || c.is_digit(10) {
-			self.read_number()
-		} else if c == '"' {
-			self.read_string()
-		} else if !c.is_whitespace() {
-			self.read_symbol()
-		} else {
-			Err(self.error_wrong_char(c, "Any"))
-		}
-	}
 
-	fn read_list(& mut self) -> Result<SExp, ReadError> {
-		let c1 = try!(self.peek().ok_or(self.error_eof()));
+	fn tokenize(mut self) -> Result<(Vec<Token>, FileLocation), ReadError> {
+		let mut tokens = Vec::new();
 
-		if c1.is_open_paren() {
-			self.advance();
-		} else {
-			return Err(self.error_wrong_char(c1, "({[" ));
-		}
-		
-		let items = try!(self.read_list_items());
-		
-		let c2 = try!(self.peek().ok_or(self.error_eof()));
-		
-		if c1.is_matching_paren(c2) {
-			return Ok(items);
-		} else {
-			return Err(self.error_paren_mismatch(c1, c2));
+		loop {
+			self.eat_white_space();
+
+			if self.peek().is_none() {
+				break;
+			}
+
+			tokens.push(try!(self.next_token()));
 		}
+
+		Ok((tokens, self.loc))
 	}
-	
-	fn read_list_items(& mut self) -> Result<SExp, ReadError> {
-		self.eat_white_space();
-		
+
+	fn next_token(& mut self) -> Result<Token, ReadError> {
+		let start = self.loc.clone();
 		let c = try!(self.peek().ok_or(self.error_eof()));
-		
-		if c.is_close_paren() {
-			Ok(SExp::Nil)
+
+		// eat_white_space() has already run, so c is guaranteed non-whitespace here;
+		// anything that isn't one of the special-cased characters below is a symbol char.
+		let ty = if c.is_open_paren() {
+			self.advance();
+			TokenType::OpenParen(c)
+		} else if c.is_close_paren() {
+			self.advance();
+			TokenType::CloseParen(c)
+		} else if c == '-' || c.is_digit(10) {
+			try!(self.lex_number())
+		} else if c == '"' {
+			try!(self.lex_string())
+		} else if c == '\'' {
+			self.advance();
+			TokenType::Quote
+		} else if c == '`' {
+			self.advance();
+			TokenType::Quasiquote
+		} else if c == ',' {
+			self.advance();
+			if self.peek() == Some('@') {
+				self.advance();
+				TokenType::UnquoteSplicing
+			} else {
+				TokenType::Unquote
+			}
 		} else {
-			let head = try!(self.read_sexp());
-			let tail = try!(self.read_list_items());
-			
-			Ok(SExp::Cons(Box::new(head), Box::new(tail)))
-		}
+			self.lex_symbol()
+		};
+
+		Ok(Token{ ty, loc: start })
 	}
-	
-	fn read_symbol(& mut self) -> Result<SExp, ReadError> {
+
+	fn lex_symbol(& mut self) -> TokenType {
 		let mut sym_string = String::new();
-		
+
 		while let Some(c) = self.peek() {
 			if c.is_delimiter() {
 				break;
@@ -102,39 +188,75 @@ impl<'a> SExpParser<'a> {
 			sym_string.push(c);
 			self.advance();
 		}
-		
-		Ok(SExp::Symbol(sym_string))
+
+		TokenType::Sym(sym_string)
 	}
-	
+
 	fn read_escaped_string_char(& mut self) -> Result<char, ReadError> {
 		let c = try!(self.next().ok_or(self.error_eof()));
-		
+
 		if c == '\\' {
 			let c = try!(self.next().ok_or(self.error_eof()));
-			Ok(
-				match c {
-					'n' => '\n',
-					't' => '\t',
-					'r' => '\r',
-					 _  => c,
-				}
-			)
+			match c {
+				'n' => Ok('\n'),
+				't' => Ok('\t'),
+				'r' => Ok('\r'),
+				'x' => self.read_hex_escape(),
+				'u' => self.read_unicode_escape(),
+				 _  => Ok(c),
+			}
 		} else {
 			Ok(c)
 		}
 	}
-	
-	fn read_string(& mut self) -> Result<SExp, ReadError> {
-		let c = try!(self.peek().ok_or(self.error_eof()));
-		
-		if c != '"' {
-			return Err(self.error_wrong_char(c, "\""));
-		} else {
-			self.advance();
+
+	fn read_hex_escape(& mut self) -> Result<char, ReadError> {
+		let mut digits = String::new();
+
+		for _ in 0..2 {
+			let c = try!(self.next().ok_or(self.error_eof()));
+			if !c.is_digit(16) {
+				return Err(self.error_malformed_escape(&format!("\\x{}{}", digits, c)));
+			}
+			digits.push(c);
+		}
+
+		let value = u32::from_str_radix(&digits, 16).unwrap();
+		Ok(value as u8 as char)
+	}
+
+	fn read_unicode_escape(& mut self) -> Result<char, ReadError> {
+		let open = try!(self.next().ok_or(self.error_eof()));
+		if open != '{' {
+			return Err(self.error_malformed_escape(&format!("\\u{}", open)));
+		}
+
+		let mut digits = String::new();
+		loop {
+			let c = try!(self.next().ok_or(self.error_eof()));
+			if c == '}' {
+				break;
+			}
+			if !c.is_digit(16) || digits.len() >= 6 {
+				return Err(self.error_malformed_escape(&format!("\\u{{{}{}", digits, c)));
+			}
+			digits.push(c);
 		}
-		
+
+		if digits.is_empty() {
+			return Err(self.error_malformed_escape("\\u{}"));
+		}
+
+		let value = u32::from_str_radix(&digits, 16).unwrap();
+
+		char::from_u32(value).ok_or_else(|| self.error_malformed_escape(&format!("\\u{{{}}}", digits)))
+	}
+
+	fn lex_string(& mut self) -> Result<TokenType, ReadError> {
+		self.advance(); // consume the opening '"' confirmed by the caller
+
 		let mut str_val = String::new();
-		
+
 		loop {
 			let c = try!(self.peek().ok_or(self.error_eof()));
 			if c == '"' {
@@ -145,19 +267,64 @@ impl<'a> SExpParser<'a> {
 				str_val.push(c);
 			}
 		}
-		
-		Ok(SExp::String(str_val))
+
+		Ok(TokenType::Str(str_val))
 	}
-	
-	fn read_number(& mut self) -> Result<SExp, ReadError>{
-		Err(self.error_not_implemented())
+
+	fn lex_number(& mut self) -> Result<TokenType, ReadError>{
+		let mut buf = String::new();
+
+		while let Some(c) = self.peek() {
+			if c.is_delimiter() {
+				break;
+			}
+			buf.push(c);
+			self.advance();
+		}
+
+		// A lone '-', or a '-' not followed by a digit, was never a number;
+		// it's a symbol that happened to share lex_number's dispatch char.
+		let second = buf.chars().nth(1);
+		if buf == "-" || (buf.starts_with('-') && !second.map_or(false, |c| c.is_digit(10))) {
+			return Ok(TokenType::Sym(buf));
+		}
+
+		let (neg, digits) = if buf.starts_with('-') {
+			(true, &buf[1..])
+		} else {
+			(false, &buf[..])
+		};
+
+		let radix = if let Some(rest) = digits.strip_prefix("0x") {
+			Some((16, rest))
+		} else if let Some(rest) = digits.strip_prefix("0o") {
+			Some((8, rest))
+		} else if let Some(rest) = digits.strip_prefix("0b") {
+			Some((2, rest))
+		} else {
+			None
+		};
+
+		if let Some((base, rest)) = radix {
+			return isize::from_str_radix(rest, base)
+				.map(|n| TokenType::Int(if neg { -n } else { n }))
+				.map_err(|_| self.error_malformed_number(&buf));
+		}
+
+		if digits.contains('.') || digits.contains('e') || digits.contains('E') {
+			return buf.parse::<f64>()
+				.map(TokenType::Float)
+				.map_err(|_| self.error_malformed_number(&buf));
+		}
+
+		buf.parse::<isize>()
+			.map(TokenType::Int)
+			.map_err(|_| self.error_malformed_number(&buf))
 	}
 
-	
 	fn advance(& mut self){
 		let _ = self.next();
 	}
-	
 
 	fn error_eof(&self) -> ReadError {
 		ReadError::EarlyEOF{
@@ -165,32 +332,29 @@ impl<'a> SExpParser<'a> {
 			msg: "Unexpected End of File".to_string(),
 		}
 	}
-	
-	fn error_wrong_char(&self, c : char, expected : &str) -> ReadError {
-		ReadError::WrongChar{
+
+	fn error_malformed_number(&self, buf : &str) -> ReadError {
+		ReadError::MalformedNumber{
 			loc: self.loc.clone(),
-			msg: format!("Unexpect character '{}' expected one of '{}'", c, expected),
+			msg: format!("Malformed numeric literal '{}'", buf),
 		}
 	}
-	
-	fn error_paren_mismatch(&self, c1 : char, c2 : char) -> ReadError {
-		ReadError::ParenMismatch{
+
+	fn error_malformed_escape(&self, escape : &str) -> ReadError {
+		ReadError::MalformedEscape{
 			loc: self.loc.clone(),
-			msg: format!("List delimiters don't match:  '{}' and '{}'", c1, c2),
+			msg: format!("Malformed escape sequence '{}'", escape),
 		}
 	}
-	
-	fn error_not_implemented(&self) -> ReadError {
-		ReadError::NotImplemented
-	}
+
 }
 
-impl<'a> Iterator for SExpParser<'a>{
+impl<'a> Iterator for Lexer<'a>{
 	type Item = char;
 	fn next(&mut self) -> Option<char>{
 		let c = self.next_char;
 		self.next_char = self.iter.next();
-		
+
 		if let Some(c) = c {
 			if c == '\n' {
 				self.loc.line += 1;
@@ -208,13 +372,138 @@ trait Peek{
 	fn peek(&self) -> Option<Self::Item>;
 }
 
-impl<'a> Peek for SExpParser<'a>{
+impl<'a> Peek for Lexer<'a>{
 	type Item = char;
 	fn peek(&self) -> Option<Self::Item>{
 		return self.next_char;
 	}
 }
 
+struct Parser{
+	tokens : Vec<Token>,
+	pos : usize,
+	eof_loc : FileLocation,
+}
+
+impl Parser {
+	fn peek(&self) -> Option<&Token> {
+		self.tokens.get(self.pos)
+	}
+
+	fn next(& mut self) -> Option<Token> {
+		if self.pos < self.tokens.len() {
+			let tok = self.tokens[self.pos].clone();
+			self.pos += 1;
+			Some(tok)
+		} else {
+			None
+		}
+	}
+
+	fn current_loc(&self) -> FileLocation {
+		match self.peek() {
+			Some(tok) => tok.loc.clone(),
+			None => self.eof_loc.clone(),
+		}
+	}
+
+	fn expect(& mut self) -> Result<Token, ReadError> {
+		let loc = self.current_loc();
+		self.next().ok_or_else(|| ReadError::EarlyEOF{
+			loc,
+			msg: "Unexpected End of File".to_string(),
+		})
+	}
+
+	fn read_sexp(& mut self) -> Result<Spanned<SExp>, ReadError> {
+		let tok = try!(self.expect());
+
+		match tok.ty {
+			TokenType::OpenParen(c) => self.read_list(c, tok.loc),
+			TokenType::CloseParen(c) => {
+				let expected = [TokenClass::OpenParen, TokenClass::StringStart, TokenClass::Number, TokenClass::Symbol, TokenClass::QuotePrefix];
+				Err(self.error_unexpected(&TokenType::CloseParen(c), tok.loc, &expected))
+			}
+			TokenType::Str(s) => Ok(self.atom(SExp::String(s), tok.loc)),
+			TokenType::Int(n) => Ok(self.atom(SExp::Integer(n), tok.loc)),
+			TokenType::Float(n) => Ok(self.atom(SExp::Float(n), tok.loc)),
+			TokenType::Sym(s) => Ok(self.atom(SExp::Symbol(s), tok.loc)),
+			TokenType::Quote => self.read_quote_form("quote", tok.loc),
+			TokenType::Quasiquote => self.read_quote_form("quasiquote", tok.loc),
+			TokenType::Unquote => self.read_quote_form("unquote", tok.loc),
+			TokenType::UnquoteSplicing => self.read_quote_form("unquote-splicing", tok.loc),
+		}
+	}
+
+	fn atom(&self, value : SExp, start : FileLocation) -> Spanned<SExp> {
+		Spanned::new(value, start, self.current_loc())
+	}
+
+	fn read_quote_form(& mut self, name : &str, prefix_loc : FileLocation) -> Result<Spanned<SExp>, ReadError> {
+		let form = try!(self.read_sexp());
+		let end = form.end.clone();
+
+		let symbol = Spanned::new(SExp::Symbol(name.to_string()), prefix_loc.clone(), prefix_loc.clone());
+		let inner = SExp::Cons(Box::new(form), Box::new(SExp::Nil));
+
+		Ok(Spanned::new(SExp::Cons(Box::new(symbol), Box::new(inner)), prefix_loc, end))
+	}
+
+	fn read_list(& mut self, open : char, start : FileLocation) -> Result<Spanned<SExp>, ReadError> {
+		let items = try!(self.read_list_items());
+
+		let tok = try!(self.expect());
+
+		match tok.ty {
+			TokenType::CloseParen(close) => {
+				if open.is_matching_paren(close) {
+					let mut end = tok.loc.clone();
+					end.col += 1;
+					Ok(Spanned::new(items, start, end))
+				} else {
+					Err(self.error_paren_mismatch(open, close, tok.loc))
+				}
+			}
+			other => Err(self.error_unexpected(&other, tok.loc, &[TokenClass::CloseParen])),
+		}
+	}
+
+	fn read_list_items(& mut self) -> Result<SExp, ReadError> {
+		match self.peek().map(|tok| tok.ty.clone()) {
+			Some(TokenType::CloseParen(_)) => Ok(SExp::Nil),
+			None => Err(self.error_eof()),
+			_ => {
+				let head = try!(self.read_sexp());
+				let tail = try!(self.read_list_items());
+
+				Ok(SExp::Cons(Box::new(head), Box::new(tail)))
+			}
+		}
+	}
+
+	fn error_eof(&self) -> ReadError {
+		ReadError::EarlyEOF{
+			loc: self.current_loc(),
+			msg: "Unexpected End of File".to_string(),
+		}
+	}
+
+	fn error_unexpected(&self, found : &TokenType, loc : FileLocation, expected : &[TokenClass]) -> ReadError {
+		let descriptions : Vec<&str> = expected.iter().map(TokenClass::describe).collect();
+		ReadError::WrongChar{
+			loc,
+			msg: format!("Unexpected {}, expected one of {}", found.describe(), descriptions.join(", ")),
+		}
+	}
+
+	fn error_paren_mismatch(&self, c1 : char, c2 : char, loc : FileLocation) -> ReadError {
+		ReadError::ParenMismatch{
+			loc,
+			msg: format!("List delimiters don't match:  '{}' and '{}'", c1, c2),
+		}
+	}
+}
+
 trait CharExt{
 	fn is_open_paren(self) -> bool;
 	fn is_close_paren(self) -> bool;
@@ -235,15 +524,286 @@ impl CharExt for char{
 	}
 
 	fn is_delimiter(self) -> bool {
-		return 
-			self.is_whitespace() || 
-			self.is_open_paren() || 
+		return
+			self.is_whitespace() ||
+			self.is_open_paren() ||
 			self.is_close_paren() ||
-			self == '"';
+			self == '"' ||
+			self == '\'' ||
+			self == '`' ||
+			self == ',';
+	}
+}
+
+impl fmt::Display for SExp {
+	fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			SExp::Nil => write!(f, "()"),
+			SExp::Symbol(ref s) => write!(f, "{}", s),
+			SExp::String(ref s) => write!(f, "\"{}\"", s),
+			SExp::Integer(n) => write!(f, "{}", n),
+			SExp::Float(n) => write!(f, "{}", n),
+			SExp::Cons(ref head, ref tail) => {
+				try!(write!(f, "({}", head.value));
+
+				let mut rest = tail;
+				loop {
+					match **rest {
+						SExp::Nil => break,
+						SExp::Cons(ref next_head, ref next_tail) => {
+							try!(write!(f, " {}", next_head.value));
+							rest = next_tail;
+						}
+						ref improper_tail => {
+							try!(write!(f, " . {}", improper_tail));
+							break;
+						}
+					}
+				}
+
+				write!(f, ")")
+			}
+		}
 	}
 }
 
+fn prompt(text : &str) {
+	print!("{}", text);
+	let _ = io::stdout().flush();
+}
+
+fn parse_buffered(buffer : &str) -> Result<Spanned<SExp>, ReadError> {
+	let mut chars = buffer.chars();
+	let first = chars.next();
+
+	let lexer = Lexer{
+		loc: FileLocation{ file: "<stdin>".to_string(), line: 1, col: 0 },
+		iter: &mut chars,
+		next_char: first,
+	};
+
+	let (tokens, eof_loc) = try!(lexer.tokenize());
+
+	let mut parser = Parser{
+		tokens,
+		pos: 0,
+		eof_loc,
+	};
+
+	parser.read_sexp()
+}
 
 fn main() {
-	println!("Hello, world!");
+	let stdin = io::stdin();
+	let mut buffer = String::new();
+	let env = eval::Environment::global();
+
+	prompt("risp> ");
+
+	for line in stdin.lock().lines() {
+		let line = match line {
+			Ok(line) => line,
+			Err(_) => break,
+		};
+
+		buffer.push_str(&line);
+		buffer.push('\n');
+
+		match parse_buffered(&buffer) {
+			Ok(sexp) => {
+				match eval::eval(&sexp.value, &sexp.start, &env) {
+					Ok(value) => println!("{}", value),
+					Err(err) => println!("{}", err),
+				}
+				buffer.clear();
+				prompt("risp> ");
+			}
+			Err(ReadError::EarlyEOF{ .. }) => {
+				prompt("...   ");
+			}
+			Err(ReadError::WrongChar{ loc, msg }) |
+			Err(ReadError::ParenMismatch{ loc, msg }) |
+			Err(ReadError::MalformedNumber{ loc, msg }) |
+			Err(ReadError::MalformedEscape{ loc, msg }) => {
+				println!("{}:{}:{}: {}", loc.file, loc.line, loc.col, msg);
+				buffer.clear();
+				prompt("risp> ");
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn lex_all(input : &str) -> Result<Vec<TokenType>, ReadError> {
+		let mut chars = input.chars();
+		let first = chars.next();
+		let lexer = Lexer{
+			loc: FileLocation{ file: "<test>".to_string(), line: 1, col: 0 },
+			iter: &mut chars,
+			next_char: first,
+		};
+		let (tokens, _) = try!(lexer.tokenize());
+		Ok(tokens.into_iter().map(|t| t.ty).collect())
+	}
+
+	fn lex_one_int(input : &str) -> isize {
+		match lex_all(input).unwrap()[0] {
+			TokenType::Int(n) => n,
+			ref other => panic!("expected an integer token, got {:?}", other),
+		}
+	}
+
+	#[test]
+	fn lone_minus_is_a_symbol() {
+		match lex_all("-").unwrap()[0] {
+			TokenType::Sym(ref s) => assert_eq!(s, "-"),
+			ref other => panic!("expected a symbol token, got {:?}", other),
+		}
+	}
+
+	#[test]
+	fn minus_not_followed_by_a_digit_is_a_symbol() {
+		match lex_all("-foo").unwrap()[0] {
+			TokenType::Sym(ref s) => assert_eq!(s, "-foo"),
+			ref other => panic!("expected a symbol token, got {:?}", other),
+		}
+	}
+
+	#[test]
+	fn negative_number_is_an_int() {
+		assert_eq!(lex_one_int("-5"), -5);
+	}
+
+	#[test]
+	fn radix_prefixes() {
+		assert_eq!(lex_one_int("0x1A"), 26);
+		assert_eq!(lex_one_int("0o17"), 15);
+		assert_eq!(lex_one_int("0b101"), 5);
+	}
+
+	#[test]
+	fn float_literal() {
+		match lex_all("3.14").unwrap()[0] {
+			TokenType::Float(n) => assert!((n - 3.14).abs() < 1e-9),
+			ref other => panic!("expected a float token, got {:?}", other),
+		}
+	}
+
+	#[test]
+	fn malformed_number_is_an_error() {
+		match lex_all("12ab") {
+			Err(ReadError::MalformedNumber{ .. }) => {}
+			other => panic!("expected a malformed number error, got {:?}", other),
+		}
+	}
+
+	#[test]
+	fn stray_close_paren_is_wrong_char_with_expected_set() {
+		match parse_buffered(")") {
+			Err(ReadError::WrongChar{ ref msg, .. }) => assert!(msg.contains("expected one of")),
+			Ok(_) => panic!("expected an error for a stray close paren"),
+			Err(other) => panic!("expected WrongChar, got {:?}", other),
+		}
+	}
+
+	#[test]
+	fn hex_escape_decodes_byte() {
+		match lex_all("\"\\x41\"").unwrap()[0] {
+			TokenType::Str(ref s) => assert_eq!(s, "A"),
+			ref other => panic!("expected a string token, got {:?}", other),
+		}
+	}
+
+	#[test]
+	fn unicode_escape_decodes_codepoint() {
+		match lex_all("\"\\u{1F600}\"").unwrap()[0] {
+			TokenType::Str(ref s) => assert_eq!(s.chars().next().unwrap() as u32, 0x1F600),
+			ref other => panic!("expected a string token, got {:?}", other),
+		}
+	}
+
+	#[test]
+	fn malformed_hex_escape_is_an_error() {
+		match lex_all("\"\\xZZ\"") {
+			Err(ReadError::MalformedEscape{ .. }) => {}
+			other => panic!("expected a malformed escape error, got {:?}", other),
+		}
+	}
+
+	#[test]
+	fn malformed_unicode_escape_is_an_error() {
+		match lex_all("\"\\u{}\"") {
+			Err(ReadError::MalformedEscape{ .. }) => {}
+			other => panic!("expected a malformed escape error, got {:?}", other),
+		}
+	}
+
+	#[test]
+	fn incomplete_list_is_early_eof() {
+		match parse_buffered("(1 2") {
+			Err(ReadError::EarlyEOF{ .. }) => {}
+			Ok(_) => panic!("expected an error for incomplete input"),
+			Err(other) => panic!("expected EarlyEOF, got {:?}", other),
+		}
+	}
+
+	#[test]
+	fn mismatched_delimiters_are_reported() {
+		match parse_buffered("(1 2]") {
+			Err(ReadError::ParenMismatch{ .. }) => {}
+			Ok(_) => panic!("expected an error for mismatched delimiters"),
+			Err(other) => panic!("expected ParenMismatch, got {:?}", other),
+		}
+	}
+
+	#[test]
+	fn spans_cover_the_full_list() {
+		let parsed = parse_buffered("(1 2 3)").unwrap();
+		assert_eq!(parsed.start.col, 0);
+		assert_eq!(parsed.end.col, 7);
+	}
+
+	#[test]
+	fn quote_prefix_expands_to_quote_form() {
+		let parsed = parse_buffered("'x").unwrap();
+		match parsed.value {
+			SExp::Cons(ref head, ref tail) => {
+				match head.value {
+					SExp::Symbol(ref s) => assert_eq!(s, "quote"),
+					ref other => panic!("expected the 'quote' symbol, got {}", other),
+				}
+				match **tail {
+					SExp::Cons(ref inner, ref rest) => {
+						match inner.value {
+							SExp::Symbol(ref s) => assert_eq!(s, "x"),
+							ref other => panic!("expected symbol 'x', got {}", other),
+						}
+						match **rest {
+							SExp::Nil => {}
+							_ => panic!("expected a proper list"),
+						}
+					}
+					ref other => panic!("expected a Cons, got {}", other),
+				}
+			}
+			ref other => panic!("expected a Cons, got {}", other),
+		}
+	}
+
+	#[test]
+	fn quasiquote_unquote_precedence() {
+		// `,x should parse as (quasiquote (unquote x)), not fall over on the
+		// adjacent reader-macro prefixes.
+		let parsed = parse_buffered("`,x").unwrap();
+		match parsed.value {
+			SExp::Cons(ref head, _) => match head.value {
+				SExp::Symbol(ref s) => assert_eq!(s, "quasiquote"),
+				ref other => panic!("expected 'quasiquote', got {}", other),
+			},
+			ref other => panic!("expected a Cons, got {}", other),
+		}
+	}
 }